@@ -0,0 +1,73 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Cap on retained console lines; oldest lines are dropped once exceeded.
+const MAX_LOG_LINES: usize = 2000;
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsoleLevel {
+    Info,
+    Warn,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsoleSource {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsoleEvent {
+    pub level: ConsoleLevel,
+    pub source: ConsoleSource,
+    pub timestamp: u64,
+    pub message: String,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl ConsoleEvent {
+    pub fn new(level: ConsoleLevel, source: ConsoleSource, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            source,
+            timestamp: now_millis(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Bounded ring buffer of sidecar console output, oldest dropped first, so the
+/// frontend can hydrate a backlog after missing the live `server-log` events.
+#[derive(Default)]
+pub struct ConsoleLog {
+    lines: Mutex<VecDeque<ConsoleEvent>>,
+}
+
+impl ConsoleLog {
+    pub fn push(&self, event: ConsoleEvent) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() >= MAX_LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(event);
+    }
+
+    pub fn snapshot(&self) -> Vec<ConsoleEvent> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.lines.lock().unwrap().clear();
+    }
+}
@@ -1,17 +1,87 @@
+mod console_log;
+mod proxy;
+
+use console_log::{ConsoleEvent, ConsoleLevel, ConsoleLog, ConsoleSource};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 use std::time::Duration;
 use tauri::{Emitter, Manager};
 use tauri_plugin_shell::process::CommandChild;
 use tauri_plugin_shell::ShellExt;
 
-/// The port the existing server.ts listens on
-const SERVER_PORT: u16 = 8556;
+/// Fallback port used only if dynamic allocation somehow can't resolve `ServerState.port`
+pub(crate) const SERVER_PORT: u16 = 8556;
+
+/// Exponential backoff parameters for sidecar auto-restart
+const RESTART_BACKOFF_BASE_MS: u64 = 500;
+const RESTART_BACKOFF_CAP_MS: u64 = 30_000;
+/// How long the server must stay up before we forgive past restarts
+const RESTART_STABLE_THRESHOLD_SECS: u64 = 60;
+/// Give up auto-restarting after this many restarts without a stable period
+const MAX_CONSECUTIVE_RESTARTS: u32 = 6;
 
 /// State to track server sidecar process
 pub struct ServerState {
-    pub port: u16,
+    /// `None` until a port has been successfully allocated (see `pick_port`)
+    pub port: Mutex<Option<u16>>,
     pub ready: Mutex<bool>,
     pub child: Mutex<Option<CommandChild>>,
+    /// Restarts since the last time the server was stable for `RESTART_STABLE_THRESHOLD_SECS`
+    pub restart_count: Mutex<u32>,
+    /// Set before an intentional kill so the supervisor doesn't treat it as a crash
+    pub shutting_down: Mutex<bool>,
+    /// Bumped on every spawn so a stale generation's events/timers can recognize
+    /// they've been superseded and no-op instead of acting on stale state
+    pub generation: AtomicU64,
+    /// Ring buffer of sidecar stdout/stderr lines, for the frontend's diagnostic console
+    pub console: ConsoleLog,
+}
+
+impl ServerState {
+    fn empty() -> Self {
+        Self {
+            port: Mutex::new(None),
+            ready: Mutex::new(false),
+            child: Mutex::new(None),
+            restart_count: Mutex::new(0),
+            shutting_down: Mutex::new(false),
+            generation: AtomicU64::new(0),
+            console: ConsoleLog::default(),
+        }
+    }
+}
+
+/// Ask the OS for a free loopback port instead of trusting a hardcoded one is free.
+fn pick_port() -> Result<u16, String> {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to find an available port: {}", e))
+        .and_then(|listener| {
+            listener
+                .local_addr()
+                .map(|addr| addr.port())
+                .map_err(|e| format!("Failed to read bound port: {}", e))
+        })
+}
+
+/// Return the already-allocated port, or allocate one now (e.g. on a manual
+/// `start_server` after an earlier allocation failure).
+fn ensure_port(app: &tauri::AppHandle) -> Result<u16, String> {
+    let state = app.state::<ServerState>();
+    let mut port_guard = state.port.lock().unwrap();
+    if let Some(port) = *port_guard {
+        return Ok(port);
+    }
+    let port = pick_port()?;
+    *port_guard = Some(port);
+    Ok(port)
+}
+
+fn backoff_for_attempt(attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    let ms = RESTART_BACKOFF_BASE_MS
+        .saturating_mul(1u64 << exponent)
+        .min(RESTART_BACKOFF_CAP_MS);
+    Duration::from_millis(ms)
 }
 
 /// Wait for the Next.js server to become ready.
@@ -49,11 +119,209 @@ async fn wait_for_server(port: u16) -> Result<(), String> {
     ))
 }
 
+/// Spawn the sidecar process, returning its event stream and handle.
+fn spawn_sidecar(
+    app: &tauri::AppHandle,
+) -> Result<
+    (
+        tokio::sync::mpsc::Receiver<tauri_plugin_shell::process::CommandEvent>,
+        CommandChild,
+    ),
+    String,
+> {
+    let port = ensure_port(app)?;
+
+    let shell = app.shell();
+    let sidecar_cmd = shell
+        .sidecar("sidecar")
+        .map_err(|e| format!("Failed to create sidecar command: {}", e))?
+        .env("PORT", port.to_string());
+    sidecar_cmd
+        .spawn()
+        .map_err(|e| format!("Failed to spawn sidecar: {}", e))
+}
+
+/// Spawn the sidecar and supervise it: stream its output, wait for readiness,
+/// navigate the webview, and auto-restart with exponential backoff if it dies
+/// unexpectedly. Re-entered on every restart.
+///
+/// Every call is tagged with a new `generation`, bumped on `ServerState`. Tasks
+/// spawned for this generation check it's still current before acting on it,
+/// so a stale timer from a generation that's since been superseded by a fresh
+/// restart can't clobber state that belongs to the generation that replaced it.
+fn spawn_and_supervise(app: tauri::AppHandle) {
+    let generation = {
+        let state = app.state::<ServerState>();
+        state.generation.fetch_add(1, Ordering::SeqCst) + 1
+    };
+
+    tauri::async_runtime::spawn(async move {
+        let (mut rx, child) = match spawn_sidecar(&app) {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("{}", e);
+                let _ = app.emit("server-failed", e);
+                return;
+            }
+        };
+
+        let port = ensure_port(&app).unwrap_or(SERVER_PORT);
+
+        if let Some(state) = app.try_state::<ServerState>() {
+            *state.child.lock().unwrap() = Some(child);
+        }
+
+        log::info!("Server sidecar spawned, waiting for port {}...", port);
+
+        // Wait for readiness, then navigate the webview to the server.
+        let ready_app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = wait_for_server(port).await;
+            let is_current_generation = ready_app
+                .try_state::<ServerState>()
+                .map(|s| s.generation.load(Ordering::SeqCst) == generation)
+                .unwrap_or(false);
+            if !is_current_generation {
+                return;
+            }
+            match result {
+                Ok(()) => {
+                    log::info!("Server is ready on port {}", port);
+                    if let Some(state) = ready_app.try_state::<ServerState>() {
+                        if let Ok(mut ready) = state.ready.lock() {
+                            *ready = true;
+                        }
+                    }
+                    let url = proxy::webview_target(port);
+                    if let Some(window) = ready_app.get_webview_window("main") {
+                        let _ = window.navigate(url.parse().unwrap());
+                        log::info!("Navigated webview to {}", url);
+                    }
+                    let _ = ready_app.emit("server-ready", port);
+
+                    // Forgive past restarts once this generation has proven stable,
+                    // unless it's since been superseded by a newer one.
+                    tokio::time::sleep(Duration::from_secs(RESTART_STABLE_THRESHOLD_SECS)).await;
+                    if let Some(state) = ready_app.try_state::<ServerState>() {
+                        if state.generation.load(Ordering::SeqCst) == generation {
+                            *state.restart_count.lock().unwrap() = 0;
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::error!("Server failed to start: {}", e);
+                    let _ = ready_app.emit("server-error", e);
+                }
+            }
+        });
+
+        // Stream sidecar output and react to unexpected termination.
+        while let Some(event) = rx.recv().await {
+            use tauri_plugin_shell::process::CommandEvent;
+            match event {
+                CommandEvent::Stdout(line) => {
+                    let text = String::from_utf8_lossy(&line).trim().to_string();
+                    log::info!("[server] {}", text);
+                    let event = ConsoleEvent::new(ConsoleLevel::Info, ConsoleSource::Stdout, text);
+                    if let Some(state) = app.try_state::<ServerState>() {
+                        state.console.push(event.clone());
+                    }
+                    let _ = app.emit("server-log", event);
+                }
+                CommandEvent::Stderr(line) => {
+                    let text = String::from_utf8_lossy(&line).trim().to_string();
+                    log::warn!("[server] {}", text);
+                    let event = ConsoleEvent::new(ConsoleLevel::Warn, ConsoleSource::Stderr, text);
+                    if let Some(state) = app.try_state::<ServerState>() {
+                        state.console.push(event.clone());
+                    }
+                    let _ = app.emit("server-log", event);
+                }
+                CommandEvent::Terminated(payload) => {
+                    let is_current_generation = app
+                        .try_state::<ServerState>()
+                        .map(|s| s.generation.load(Ordering::SeqCst) == generation)
+                        .unwrap_or(false);
+                    if !is_current_generation {
+                        log::info!(
+                            "[server] Stale sidecar generation {} terminated, ignoring",
+                            generation
+                        );
+                        return;
+                    }
+
+                    let shutting_down = app
+                        .try_state::<ServerState>()
+                        .map(|s| *s.shutting_down.lock().unwrap())
+                        .unwrap_or(false);
+                    if shutting_down {
+                        log::info!("Shutdown was intentional, not restarting sidecar");
+                        return;
+                    }
+
+                    log::error!("[server] Process terminated with code: {:?}", payload.code);
+                    let _ = app.emit("server-crashed", payload.code);
+
+                    let restart_count = match app.try_state::<ServerState>() {
+                        Some(state) => {
+                            let mut count = state.restart_count.lock().unwrap();
+                            *count += 1;
+                            *count
+                        }
+                        None => return,
+                    };
+
+                    if restart_count > MAX_CONSECUTIVE_RESTARTS {
+                        log::error!(
+                            "Giving up after {} consecutive restarts",
+                            restart_count - 1
+                        );
+                        // Drop the dead child so `start_server`/`restart_server` can
+                        // recover instead of permanently seeing one "already running".
+                        if let Some(state) = app.try_state::<ServerState>() {
+                            *state.child.lock().unwrap() = None;
+                        }
+                        let _ = app.emit("server-failed", "too many consecutive restarts");
+                        return;
+                    }
+
+                    let backoff = backoff_for_attempt(restart_count);
+                    log::warn!(
+                        "Restarting sidecar in {:?} (attempt {})",
+                        backoff,
+                        restart_count
+                    );
+                    tokio::time::sleep(backoff).await;
+
+                    // Re-check: stop_server may have run during the backoff sleep.
+                    let shutting_down = app
+                        .try_state::<ServerState>()
+                        .map(|s| *s.shutting_down.lock().unwrap())
+                        .unwrap_or(false);
+                    if shutting_down {
+                        log::info!("Shutdown requested during backoff, aborting restart");
+                        return;
+                    }
+
+                    spawn_and_supervise(app.clone());
+                    return;
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
 /// Get server health status
 #[tauri::command]
-async fn server_health() -> Result<String, String> {
+async fn server_health(state: tauri::State<'_, ServerState>) -> Result<String, String> {
+    let port = state
+        .port
+        .lock()
+        .unwrap()
+        .ok_or_else(|| "Server is not running: no port was allocated".to_string())?;
     let client = reqwest::Client::new();
-    let url = format!("http://localhost:{}", SERVER_PORT);
+    let url = format!("http://localhost:{}", port);
 
     let response = client
         .get(&url)
@@ -63,15 +331,97 @@ async fn server_health() -> Result<String, String> {
 
     Ok(format!(
         "{{\"status\":\"ok\",\"port\":{},\"statusCode\":{}}}",
-        SERVER_PORT,
+        port,
         response.status().as_u16()
     ))
 }
 
+/// Kill the running sidecar, marking the shutdown intentional so the
+/// supervisor doesn't treat it as a crash and auto-restart it.
+async fn do_stop(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<ServerState>();
+    *state.shutting_down.lock().unwrap() = true;
+    let child = state.child.lock().unwrap().take();
+    if let Some(child) = child {
+        child
+            .kill()
+            .map_err(|e| format!("Failed to kill sidecar: {}", e))?;
+    }
+    let _ = app.emit("server-stopped", ());
+    Ok(())
+}
+
+/// Re-spawn the sidecar and resume supervising it. Errors rather than spawning
+/// a second sidecar if one is already running.
+fn do_start(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<ServerState>();
+    if state.child.lock().unwrap().is_some() {
+        return Err("Sidecar is already running".to_string());
+    }
+    *state.shutting_down.lock().unwrap() = false;
+    // Give the new sidecar a fresh backoff budget instead of inheriting one
+    // that's already exhausted from a supervisor give-up.
+    *state.restart_count.lock().unwrap() = 0;
+    spawn_and_supervise(app.clone());
+    Ok(())
+}
+
+/// Stop the sidecar without restarting it. Lets users recover from a wedged
+/// Next.js build without quitting the whole app.
+#[tauri::command]
+async fn stop_server(app: tauri::AppHandle) -> Result<(), String> {
+    do_stop(&app).await
+}
+
+/// Manually start the sidecar, e.g. after it was stopped with `stop_server`.
+#[tauri::command]
+async fn start_server(app: tauri::AppHandle) -> Result<(), String> {
+    do_start(&app)
+}
+
+/// Stop then start the sidecar, giving the frontend an explicit abort control
+/// for a wedged cold start instead of waiting out all 300 attempts.
+#[tauri::command]
+async fn restart_server(app: tauri::AppHandle) -> Result<(), String> {
+    do_stop(&app).await?;
+    do_start(&app)
+}
+
+/// Fetch the buffered sidecar console backlog, for a freshly opened window
+#[tauri::command]
+fn get_server_logs(state: tauri::State<ServerState>) -> Vec<ConsoleEvent> {
+    state.console.snapshot()
+}
+
+/// Clear the buffered sidecar console backlog
+#[tauri::command]
+fn clear_server_logs(state: tauri::State<ServerState>) {
+    state.console.clear();
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
+        .register_asynchronous_uri_scheme_protocol("app", |ctx, request, responder| {
+            #[cfg(not(feature = "http-fallback"))]
+            {
+                let app = ctx.app_handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    responder.respond(proxy::proxy_to_sidecar(&app, request).await);
+                });
+            }
+            #[cfg(feature = "http-fallback")]
+            {
+                let _ = (ctx, request);
+                responder.respond(
+                    tauri::http::Response::builder()
+                        .status(404)
+                        .body(Vec::new())
+                        .unwrap(),
+                );
+            }
+        })
         .setup(|app| {
             if cfg!(debug_assertions) {
                 app.handle().plugin(
@@ -81,78 +431,21 @@ pub fn run() {
                 )?;
             }
 
-            // Spawn the existing server.ts as sidecar
-            let shell = app.shell();
-            let sidecar_cmd = shell.sidecar("sidecar").map_err(|e| {
-                log::error!("Failed to create sidecar command: {}", e);
-                e
-            })?;
-
-            let (mut rx, child) = sidecar_cmd.spawn().map_err(|e| {
-                log::error!("Failed to spawn sidecar: {}", e);
-                e
-            })?;
-
-            log::info!("Server sidecar spawned, waiting for port {}...", SERVER_PORT);
-
-            // Track server state including child process for cleanup
-            app.manage(ServerState {
-                port: SERVER_PORT,
-                ready: Mutex::new(false),
-                child: Mutex::new(Some(child)),
-            });
-
-            // Log sidecar output
-            let handle_log = app.handle().clone();
-            tauri::async_runtime::spawn(async move {
-                use tauri_plugin_shell::process::CommandEvent;
-                while let Some(event) = rx.recv().await {
-                    match event {
-                        CommandEvent::Stdout(line) => {
-                            let text = String::from_utf8_lossy(&line);
-                            log::info!("[server] {}", text.trim());
-                        }
-                        CommandEvent::Stderr(line) => {
-                            let text = String::from_utf8_lossy(&line);
-                            log::warn!("[server] {}", text.trim());
-                        }
-                        CommandEvent::Terminated(payload) => {
-                            log::error!(
-                                "[server] Process terminated with code: {:?}",
-                                payload.code
-                            );
-                            let _ = handle_log.emit("server-crashed", payload.code);
-                        }
-                        _ => {}
-                    }
+            // Track server state including child process for cleanup. Always
+            // managed, even if port allocation fails below, so commands that
+            // extract `ServerState` never panic on a missing managed type.
+            app.manage(ServerState::empty());
+
+            match pick_port() {
+                Ok(port) => {
+                    *app.state::<ServerState>().port.lock().unwrap() = Some(port);
+                    spawn_and_supervise(app.handle().clone());
                 }
-            });
-
-            // Wait for server readiness, then navigate webview to the server
-            let handle = app.handle().clone();
-            tauri::async_runtime::spawn(async move {
-                match wait_for_server(SERVER_PORT).await {
-                    Ok(()) => {
-                        log::info!("Server is ready on port {}", SERVER_PORT);
-                        if let Some(state) = handle.try_state::<ServerState>() {
-                            if let Ok(mut ready) = state.ready.lock() {
-                                *ready = true;
-                            }
-                        }
-                        // Navigate the main webview to the server URL
-                        let url = format!("http://localhost:{}", SERVER_PORT);
-                        if let Some(window) = handle.get_webview_window("main") {
-                            let _ = window.navigate(url.parse().unwrap());
-                            log::info!("Navigated webview to {}", url);
-                        }
-                        let _ = handle.emit("server-ready", SERVER_PORT);
-                    }
-                    Err(e) => {
-                        log::error!("Server failed to start: {}", e);
-                        let _ = handle.emit("server-error", e);
-                    }
+                Err(e) => {
+                    log::error!("{}", e);
+                    let _ = app.emit("server-port-unavailable", e);
                 }
-            });
+            }
 
             Ok(())
         })
@@ -161,6 +454,7 @@ pub fn run() {
             if let tauri::WindowEvent::Destroyed = event {
                 let app = window.app_handle();
                 if let Some(state) = app.try_state::<ServerState>() {
+                    *state.shutting_down.lock().unwrap() = true;
                     if let Ok(mut child_guard) = state.child.lock() {
                         if let Some(child) = child_guard.take() {
                             log::info!("Shutting down server sidecar...");
@@ -170,13 +464,21 @@ pub fn run() {
                 }
             }
         })
-        .invoke_handler(tauri::generate_handler![server_health])
+        .invoke_handler(tauri::generate_handler![
+            server_health,
+            get_server_logs,
+            clear_server_logs,
+            start_server,
+            stop_server,
+            restart_server
+        ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
         .run(|app, event| {
             // Kill sidecar when app exits
             if let tauri::RunEvent::Exit = event {
                 if let Some(state) = app.try_state::<ServerState>() {
+                    *state.shutting_down.lock().unwrap() = true;
                     if let Ok(mut child_guard) = state.child.lock() {
                         if let Some(child) = child_guard.take() {
                             log::info!("App exiting, shutting down server sidecar...");
@@ -0,0 +1,93 @@
+//! Forwards webview requests into the sidecar over loopback HTTP, behind the
+//! `app://` custom URI scheme, so the webview never has to hold a direct
+//! `http://localhost:<port>` navigation.
+
+use crate::ServerState;
+
+/// The URL the main webview is pointed at once the sidecar is ready.
+#[cfg(feature = "http-fallback")]
+pub fn webview_target(port: u16) -> String {
+    format!("http://localhost:{}", port)
+}
+
+#[cfg(not(feature = "http-fallback"))]
+pub fn webview_target(_port: u16) -> String {
+    "app://localhost".to_string()
+}
+
+/// Headers that describe a single hop's connection/framing rather than the
+/// resource itself. We fully buffer the body on both sides of this proxy, so
+/// e.g. forwarding a sidecar's `transfer-encoding: chunked` verbatim onto an
+/// already-dechunked body would lie to the webview about the framing.
+#[cfg(not(feature = "http-fallback"))]
+fn is_hop_by_hop_header(name: &str) -> bool {
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "connection"
+            | "keep-alive"
+            | "proxy-authenticate"
+            | "proxy-authorization"
+            | "te"
+            | "trailer"
+            | "transfer-encoding"
+            | "upgrade"
+    )
+}
+
+/// Convert an incoming `app://` request into a sidecar HTTP request and back.
+#[cfg(not(feature = "http-fallback"))]
+pub async fn proxy_to_sidecar(
+    app: &tauri::AppHandle,
+    request: tauri::http::Request<Vec<u8>>,
+) -> tauri::http::Response<Vec<u8>> {
+    use tauri::Manager;
+
+    let port = app
+        .try_state::<ServerState>()
+        .and_then(|s| *s.port.lock().unwrap())
+        .unwrap_or(crate::SERVER_PORT);
+
+    let (parts, body) = request.into_parts();
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let url = format!("http://localhost:{}{}", port, path_and_query);
+
+    let client = reqwest::Client::new();
+    let mut builder = client.request(parts.method.clone(), &url);
+    for (name, value) in parts.headers.iter() {
+        if is_hop_by_hop_header(name.as_str()) {
+            continue;
+        }
+        builder = builder.header(name, value);
+    }
+    builder = builder.body(body);
+
+    let response = match builder.send().await {
+        Ok(resp) => resp,
+        Err(e) => {
+            log::error!("app:// proxy request to {} failed: {}", url, e);
+            return tauri::http::Response::builder()
+                .status(502)
+                .body(Vec::new())
+                .unwrap();
+        }
+    };
+
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = response.bytes().await.unwrap_or_default();
+
+    let mut response_builder = tauri::http::Response::builder().status(status.as_u16());
+    for (name, value) in headers.iter() {
+        if is_hop_by_hop_header(name.as_str()) {
+            continue;
+        }
+        response_builder = response_builder.header(name, value);
+    }
+    response_builder
+        .body(bytes.to_vec())
+        .unwrap_or_else(|_| tauri::http::Response::builder().status(500).body(Vec::new()).unwrap())
+}